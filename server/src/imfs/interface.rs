@@ -1,17 +1,130 @@
 use std::{
-    io,
+    collections::HashSet,
+    fs,
+    io::{self, Read, Write},
     path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+    time::SystemTime,
 };
 
+use rayon::prelude::*;
+
 use crate::path_map::PathMap;
 
 pub trait ImfsFetcher {
     fn read_item(&self, path: impl AsRef<Path>) -> io::Result<ImfsItem>;
     fn read_children(&self, path: impl AsRef<Path>) -> io::Result<Vec<ImfsItem>>;
     fn read_contents(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>>;
+    fn read_metadata(&self, path: impl AsRef<Path>) -> io::Result<ImfsFileMetadata>;
     fn create_directory(&self, path: impl AsRef<Path>) -> io::Result<()>;
     fn write_contents(&self, path: impl AsRef<Path>, contents: &[u8]) -> io::Result<()>;
     fn remove(&self, path: impl AsRef<Path>) -> io::Result<()>;
+
+    /// Moves the item at `source` to `target` on disk, returning whether the
+    /// move actually happened.
+    ///
+    /// `Ok(false)` means the fetcher no-op'd against real disk state (for
+    /// example because `options.ignore_if_exists`/`ignore_if_missing`
+    /// applied to a target/source that isn't cached yet); the caller must
+    /// not re-key its cache mirror in that case.
+    fn rename(
+        &self,
+        source: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+        options: ImfsMoveOptions,
+    ) -> io::Result<bool>;
+
+    /// Copies the item at `source` to `target` on disk, returning whether the
+    /// copy actually happened, with the same `Ok(false)` semantics as `rename`.
+    fn copy(
+        &self,
+        source: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+        options: ImfsMoveOptions,
+    ) -> io::Result<bool>;
+
+    /// Starts watching `path` for changes, if this fetcher supports it.
+    ///
+    /// Events observed after this call are delivered through the channel
+    /// returned by `change_receiver`.
+    fn watch(&self, path: impl AsRef<Path>);
+
+    /// Returns the receiving half of this fetcher's change event channel.
+    ///
+    /// Fetchers that can't watch the filesystem (e.g. one backed entirely by
+    /// an in-memory mock) can return a receiver that never yields anything.
+    fn change_receiver(&self) -> Receiver<(PathBuf, ChangeKind)>;
+}
+
+/// Options controlling how `Imfs::rename` and `Imfs::copy` treat an existing
+/// or missing endpoint, mirroring a typical `Fs`-style move/copy call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImfsMoveOptions {
+    /// Replace `target` if it already exists instead of failing.
+    pub overwrite: bool,
+    /// Silently do nothing if `target` already exists.
+    pub ignore_if_exists: bool,
+    /// Silently do nothing if `source` doesn't exist.
+    pub ignore_if_missing: bool,
+}
+
+/// The cheap-to-stat portion of a file's state, used to decide whether a
+/// change event is worth re-reading contents for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImfsFileMetadata {
+    pub mtime: TruncatedTimestamp,
+    pub size: u64,
+}
+
+/// A file modification time, stored as whole seconds plus nanoseconds since
+/// the Unix epoch.
+///
+/// Borrowed from Mercurial's dirstate. `new` keeps both parts exactly as
+/// given; nothing here truncates them to a coarser precision, so two
+/// timestamps compare equal only if the fetcher reported identical values.
+/// A timestamp in the same second as the current time is treated as
+/// ambiguous (see `is_ambiguous`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TruncatedTimestamp {
+    seconds: i64,
+    nanos: u32,
+}
+
+impl TruncatedTimestamp {
+    pub fn new(seconds: i64, nanos: u32) -> Self {
+        Self { seconds, nanos }
+    }
+
+    pub fn now() -> Self {
+        let duration = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Self::new(duration.as_secs() as i64, duration.subsec_nanos())
+    }
+
+    /// Whether this timestamp falls within the same second as `now`, making
+    /// it too recent to trust: a file modified and re-stat'd within the same
+    /// second can report an identical mtime.
+    fn is_ambiguous(&self, now: &TruncatedTimestamp) -> bool {
+        self.seconds >= now.seconds
+    }
+}
+
+/// Describes the kind of change reported by an `ImfsFetcher`'s watcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+/// A change that was actually applied to the in-memory filesystem, as
+/// opposed to a raw event that turned out not to affect a resident path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImfsChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
 }
 
 pub struct Imfs<F> {
@@ -56,35 +169,177 @@ impl<F: ImfsFetcher> Imfs<F> {
         self.read_if_not_exists(path.as_ref());
         let item = self.inner.get(path.as_ref())?;
 
-        let is_file = match item {
-            ImfsItem::File(_) => true,
-            ImfsItem::Directory(_) => false,
+        let (is_file, is_directory, target) = match item {
+            ImfsItem::File(_) => (true, false, None),
+            ImfsItem::Directory(_) => (false, true, None),
+            ImfsItem::Symlink(link) => (false, false, Some(link.target.clone())),
         };
 
         Some(ImfsEntry {
             path: item.path().to_path_buf(),
             is_file,
+            is_directory,
+            target,
         })
     }
 
+    /// Follows a chain of symlinks starting at `path`, returning the path of
+    /// the first item that isn't itself a symlink.
+    ///
+    /// Returns an error rather than looping forever if the chain revisits a
+    /// path already on the resolution stack.
+    fn resolve_symlink(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let mut current = path.to_path_buf();
+        let mut visited = vec![current.clone()];
+
+        loop {
+            self.read_if_not_exists(&current);
+
+            match self.inner.get(&current) {
+                Some(ImfsItem::Symlink(link)) => {
+                    let target = link.target.clone();
+
+                    if visited.contains(&target) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("symlink cycle detected while resolving {}", path.display()),
+                        ));
+                    }
+
+                    visited.push(target.clone());
+                    current = target;
+                }
+                _ => return Ok(current),
+            }
+        }
+    }
+
     pub fn get_contents(&mut self, path: impl AsRef<Path>) -> Option<&[u8]> {
-        self.read_if_not_exists(path.as_ref());
+        let resolved = self.resolve_symlink(path.as_ref()).ok()?;
+        self.read_if_not_exists(&resolved);
 
-        match self.inner.get_mut(path.as_ref())? {
+        match self.inner.get_mut(&resolved)? {
             ImfsItem::File(file) => {
                 if file.contents.is_none() {
-                    file.contents = Some(self.fetcher.read_contents(path)
-                        .expect("TODO: Handle this error"));
+                    let contents = self.fetcher.read_contents(&resolved)
+                        .expect("TODO: Handle this error");
+                    file.digest = Some(blake3::hash(&contents));
+                    file.contents = Some(contents);
                 }
 
                 Some(file.contents.as_ref().unwrap())
             }
-            ImfsItem::Directory(_) => None
+            ImfsItem::Directory(_) | ImfsItem::Symlink(_) => None,
+        }
+    }
+
+    /// Compares a cached file's stat info against a fresh read, returning
+    /// `Unsure` whenever there isn't enough cached information to say for
+    /// certain whether the file changed.
+    fn file_status(&self, file: &ImfsFile, metadata: &ImfsFileMetadata) -> FileStatus {
+        let (cached_mtime, cached_size) = match (file.mtime, file.size) {
+            (Some(mtime), Some(size)) => (mtime, size),
+            _ => return FileStatus::Unsure,
+        };
+
+        if cached_size != metadata.size {
+            return FileStatus::Dirty;
+        }
+
+        if cached_mtime != metadata.mtime {
+            return FileStatus::Unsure;
+        }
+
+        if cached_mtime.is_ambiguous(&TruncatedTimestamp::now()) {
+            FileStatus::Unsure
+        } else {
+            FileStatus::Clean
+        }
+    }
+
+    /// Updates a resident file's cached stat info, and its contents/digest
+    /// if they were freshly read. Passing `None` for `fresh` just
+    /// invalidates the cached contents so they're re-read lazily later.
+    fn refresh_file(
+        &mut self,
+        path: &Path,
+        metadata: ImfsFileMetadata,
+        fresh: Option<(Vec<u8>, blake3::Hash)>,
+    ) {
+        if let Some(ImfsItem::File(file)) = self.inner.get_mut(path) {
+            file.mtime = Some(metadata.mtime);
+            file.size = Some(metadata.size);
+
+            match fresh {
+                Some((contents, digest)) => {
+                    file.digest = Some(digest);
+                    file.contents = Some(contents);
+                }
+                None => {
+                    file.digest = None;
+                    file.contents = None;
+                }
+            }
+        }
+    }
+
+    /// Reconciles an already-enumerated, resident directory's cached
+    /// children against a fresh listing, touching only what changed: a
+    /// child that disappeared has its whole subtree purged, and a child
+    /// that's new is inserted, but an already-cached child is left alone so
+    /// a change event on the directory doesn't clobber a sibling's
+    /// already-cached mtime/digest.
+    ///
+    /// Returns a `Removed` change if the directory itself vanished, mirroring
+    /// the `NotFound` handling in `commit_change`.
+    fn refresh_directory_children(&mut self, path: &Path) -> io::Result<Option<ImfsChange>> {
+        match self.fetcher.read_children(path) {
+            Ok(children) => {
+                let fresh_paths: HashSet<PathBuf> =
+                    children.iter().map(|child| child.path().to_path_buf()).collect();
+
+                let stale_children = self
+                    .inner
+                    .children(path)
+                    .map(|paths| {
+                        paths
+                            .into_iter()
+                            .map(|path| path.to_path_buf())
+                            .filter(|path| !fresh_paths.contains(path))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+
+                for stale in stale_children {
+                    self.remove_subtree(&stale);
+                }
+
+                for child in children {
+                    let child_path = child.path().to_path_buf();
+
+                    if !self.inner.contains_key(&child_path) {
+                        self.inner.insert(child_path, child);
+                    }
+                }
+
+                Ok(None)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.remove_subtree(path);
+
+                Ok(Some(ImfsChange {
+                    path: path.to_path_buf(),
+                    kind: ChangeKind::Removed,
+                }))
+            }
+            Err(err) => Err(err),
         }
     }
 
     pub fn get_children(&mut self, path: impl AsRef<Path>) -> Option<Vec<ImfsEntry>> {
-        self.inner.children(path)?
+        let resolved = self.resolve_symlink(path.as_ref()).ok()?;
+
+        self.inner.children(&resolved)?
             .into_iter()
             .map(|path| path.to_path_buf())
             .collect::<Vec<PathBuf>>()
@@ -92,11 +347,726 @@ impl<F: ImfsFetcher> Imfs<F> {
             .map(|path| self.get(path))
             .collect()
     }
+
+    /// Applies a single filesystem change event to the in-memory tree.
+    ///
+    /// Returns `None` if the event doesn't touch a path that `would_be_resident`,
+    /// in which case there's nothing for the rest of the system to react to.
+    pub fn commit_change(&mut self, path: &Path, kind: ChangeKind) -> io::Result<Option<ImfsChange>> {
+        if !self.would_be_resident(path) {
+            return Ok(None);
+        }
+
+        match kind {
+            ChangeKind::Removed => {
+                // `path` may be a directory; purge its cached descendants
+                // too; a bare single-key remove would leave them behind as
+                // dangling entries that later reads panic on.
+                self.remove_subtree(path);
+            }
+            ChangeKind::Created | ChangeKind::Modified => {
+                match self.inner.get(path) {
+                    Some(ImfsItem::File(existing)) => {
+                        match self.fetcher.read_metadata(path) {
+                            Ok(metadata) => match self.file_status(existing, &metadata) {
+                                FileStatus::Clean => return Ok(None),
+                                FileStatus::Dirty => self.refresh_file(path, metadata, None),
+                                FileStatus::Unsure => {
+                                    let contents = self.fetcher.read_contents(path)?;
+                                    let digest = blake3::hash(&contents);
+                                    let unchanged = existing.digest == Some(digest);
+
+                                    self.refresh_file(path, metadata, Some((contents, digest)));
+
+                                    if unchanged {
+                                        return Ok(None);
+                                    }
+                                }
+                            },
+                            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                                self.remove_subtree(path);
+
+                                return Ok(Some(ImfsChange {
+                                    path: path.to_path_buf(),
+                                    kind: ChangeKind::Removed,
+                                }));
+                            }
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    // A change event on a directory that's already been
+                    // enumerated (e.g. fired because a child changed) should
+                    // only touch what actually changed among its children,
+                    // not blow away every sibling's cached state.
+                    Some(ImfsItem::Directory(dir)) if dir.children_enumerated => {
+                        if let Some(change) = self.refresh_directory_children(path)? {
+                            return Ok(Some(change));
+                        }
+                    }
+                    _ => {
+                        match self.fetcher.read_item(path) {
+                            Ok(ImfsItem::Directory(mut dir)) => {
+                                let children = self.fetcher.read_children(path)?;
+                                dir.children_enumerated = true;
+                                self.inner.insert(path.to_path_buf(), ImfsItem::Directory(dir));
+
+                                for child in children {
+                                    self.inner.insert(child.path().to_path_buf(), child);
+                                }
+                            }
+                            Ok(ImfsItem::File(file)) => {
+                                self.inner.insert(path.to_path_buf(), ImfsItem::File(file));
+                            }
+                            Ok(ImfsItem::Symlink(link)) => {
+                                self.inner.insert(path.to_path_buf(), ImfsItem::Symlink(link));
+                            }
+                            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                                self.remove_subtree(path);
+
+                                return Ok(Some(ImfsChange {
+                                    path: path.to_path_buf(),
+                                    kind: ChangeKind::Removed,
+                                }));
+                            }
+                            Err(err) => return Err(err),
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Some(ImfsChange {
+            path: path.to_path_buf(),
+            kind,
+        }))
+    }
+
+    /// Moves the item at `source` to `target`, both on disk and in the
+    /// cached tree, re-keying the moved subtree without forcing a re-read of
+    /// already-cached contents.
+    pub fn rename(
+        &mut self,
+        source: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+        options: ImfsMoveOptions,
+    ) -> io::Result<()> {
+        let source = source.as_ref();
+        let target = target.as_ref();
+
+        if options.ignore_if_missing && !self.inner.contains_key(source) {
+            return Ok(());
+        }
+
+        let target_exists = self.inner.contains_key(target);
+
+        if target_exists {
+            if options.ignore_if_exists {
+                return Ok(());
+            }
+
+            if !options.overwrite {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("cannot rename to {}: already exists", target.display()),
+                ));
+            }
+        }
+
+        // Cache residency doesn't mean disk truth: `Imfs` is a lazy, partial
+        // mirror, so a target/source this check sees as missing may well
+        // exist on disk already. Trust the fetcher's report of whether it
+        // actually performed the move before touching the cache.
+        if !self.fetcher.rename(source, target, options)? {
+            return Ok(());
+        }
+
+        if target_exists {
+            // The on-disk overwrite just replaced everything under `target`;
+            // drop any cached descendants that aren't part of `source`'s
+            // subtree before re-keying it into place.
+            self.remove_subtree(target);
+        }
+
+        self.move_subtree(source, target);
+
+        Ok(())
+    }
+
+    /// Copies the item at `source` to `target`, both on disk and in the
+    /// cached tree.
+    pub fn copy(
+        &mut self,
+        source: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+        options: ImfsMoveOptions,
+    ) -> io::Result<()> {
+        let source = source.as_ref();
+        let target = target.as_ref();
+
+        if options.ignore_if_missing && !self.inner.contains_key(source) {
+            return Ok(());
+        }
+
+        let target_exists = self.inner.contains_key(target);
+
+        if target_exists {
+            if options.ignore_if_exists {
+                return Ok(());
+            }
+
+            if !options.overwrite {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("cannot copy to {}: already exists", target.display()),
+                ));
+            }
+        }
+
+        // See the matching comment in `rename`: trust the fetcher's report of
+        // whether it actually performed the copy before touching the cache.
+        if !self.fetcher.copy(source, target, options)? {
+            return Ok(());
+        }
+
+        if target_exists {
+            // The on-disk overwrite just replaced everything under `target`;
+            // drop any cached descendants that aren't part of `source`'s
+            // subtree before re-keying it into place.
+            self.remove_subtree(target);
+        }
+
+        self.copy_subtree(source, target);
+
+        Ok(())
+    }
+
+    /// Recursively removes `path` and every cached descendant of it from the
+    /// tree, used to purge a stale cached subtree before an overwriting
+    /// rename/copy re-keys a new one into its place.
+    fn remove_subtree(&mut self, path: &Path) {
+        let children = self.inner.children(path)
+            .map(|paths| paths.into_iter().map(|path| path.to_path_buf()).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        for child in children {
+            self.remove_subtree(&child);
+        }
+
+        self.inner.remove(path);
+    }
+
+    /// Re-keys a cached subtree from `source` to `target` in place,
+    /// preserving each item's already-cached `contents`/`children_enumerated`
+    /// state so a rename doesn't force a full re-read.
+    fn move_subtree(&mut self, source: &Path, target: &Path) {
+        let item = match self.inner.remove(source) {
+            Some(item) => item,
+            None => return,
+        };
+
+        let children = self.inner.children(source)
+            .map(|paths| paths.into_iter().map(|path| path.to_path_buf()).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let mut item = item;
+        item.set_path(target.to_path_buf());
+        self.inner.insert(target.to_path_buf(), item);
+
+        for child_source in children {
+            if let Ok(suffix) = child_source.strip_prefix(source) {
+                let child_target = target.join(suffix);
+                self.move_subtree(&child_source, &child_target);
+            }
+        }
+    }
+
+    /// Clones a cached subtree from `source` to `target` in place, carrying
+    /// over each item's already-cached state the same way `move_subtree` does.
+    fn copy_subtree(&mut self, source: &Path, target: &Path) {
+        let item = match self.inner.get(source) {
+            Some(item) => item.clone(),
+            None => return,
+        };
+
+        let children = self.inner.children(source)
+            .map(|paths| paths.into_iter().map(|path| path.to_path_buf()).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let mut item = item;
+        item.set_path(target.to_path_buf());
+        self.inner.insert(target.to_path_buf(), item);
+
+        for child_source in children {
+            if let Ok(suffix) = child_source.strip_prefix(source) {
+                let child_target = target.join(suffix);
+                self.copy_subtree(&child_source, &child_target);
+            }
+        }
+    }
+
+    /// Eagerly populates the tree starting at `root` by walking it in
+    /// parallel with rayon, instead of relying on lazy `get`/`get_children`
+    /// calls to discover it one directory at a time.
+    ///
+    /// Directories that fail to enumerate (e.g. permission denied) are
+    /// recorded as empty-but-enumerated rather than aborting the walk.
+    pub fn snapshot(&mut self, root: impl AsRef<Path>) -> io::Result<()>
+    where
+        F: Sync,
+    {
+        let root = root.as_ref();
+        let root_item = self.fetcher.read_item(root)?;
+        let items = snapshot_subtree(&self.fetcher, root_item);
+
+        // All of the reading above happened in parallel; only this final
+        // merge into `inner` needs exclusive access to the tree.
+        for item in items {
+            self.inner.insert(item.path().to_path_buf(), item);
+        }
+
+        Ok(())
+    }
+
+    /// Drains a batch of raw change events and applies each one to the
+    /// in-memory tree, returning the coalesced list of changes that actually
+    /// affected resident paths.
+    pub fn process_events(
+        &mut self,
+        events: impl IntoIterator<Item = (PathBuf, ChangeKind)>,
+    ) -> io::Result<Vec<ImfsChange>> {
+        let mut changes = Vec::new();
+
+        for (path, kind) in events {
+            if let Some(change) = self.commit_change(&path, kind)? {
+                changes.push(change);
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+/// Persists an `Imfs`'s tree to a single append-only log on disk, so a
+/// later run can reconstruct it in O(changes) instead of re-reading
+/// everything from scratch.
+///
+/// Borrows the dirstate-v2 append-then-compact design: each resolved change
+/// is appended as its own record, and the log is only rewritten wholesale
+/// once enough of it is dead weight.
+pub struct ImfsSnapshotStore {
+    path: PathBuf,
+    total_records: u64,
+    superseded_records: u64,
+    /// Paths whose most recently appended record is still live, i.e. hasn't
+    /// itself been superseded by a later upsert/remove. Used to decide
+    /// whether the next record written for a path makes a prior one dead
+    /// weight.
+    live_paths: HashSet<PathBuf>,
+}
+
+impl ImfsSnapshotStore {
+    /// Once more than this fraction of records in the log are superseded or
+    /// unreachable, `append_change` rewrites a compacted snapshot instead of
+    /// appending further.
+    const COMPACTION_THRESHOLD: f64 = 0.5;
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            total_records: 0,
+            superseded_records: 0,
+            live_paths: HashSet::new(),
+        }
+    }
+
+    /// Reconstructs an `Imfs` by replaying every record in the log in
+    /// order, last write per path winning. Missing log files are treated as
+    /// an empty tree rather than an error.
+    pub fn load<F: ImfsFetcher>(&mut self, fetcher: F) -> io::Result<Imfs<F>> {
+        let mut inner = PathMap::new();
+        self.total_records = 0;
+        self.superseded_records = 0;
+        self.live_paths = HashSet::new();
+
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Imfs { inner, fetcher });
+            }
+            Err(err) => return Err(err),
+        };
+        let mut reader = io::BufReader::new(file);
+
+        while let Some(record) = read_record(&mut reader)? {
+            self.total_records += 1;
+
+            match record {
+                SnapshotRecord::Upsert { path, item } => {
+                    if !self.live_paths.insert(path.clone()) {
+                        self.superseded_records += 1;
+                    }
+                    inner.insert(path.clone(), item.into_imfs_item(path));
+                }
+                SnapshotRecord::Remove { path } => {
+                    if self.live_paths.remove(&path) {
+                        self.superseded_records += 1;
+                    }
+                    inner.remove(&path);
+                }
+            }
+        }
+
+        Ok(Imfs { inner, fetcher })
+    }
+
+    /// Appends a resolved change to the log, compacting first if the log has
+    /// accumulated enough dead weight to be worth rewriting.
+    pub fn append_change<F: ImfsFetcher>(
+        &mut self,
+        imfs: &Imfs<F>,
+        change: &ImfsChange,
+    ) -> io::Result<()> {
+        if self.should_compact() {
+            self.compact(imfs)?;
+        }
+
+        let record = match imfs.inner.get(&change.path) {
+            Some(item) => SnapshotRecord::Upsert {
+                path: change.path.clone(),
+                item: PersistedItem::from_item(item),
+            },
+            None => SnapshotRecord::Remove {
+                path: change.path.clone(),
+            },
+        };
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        write_record(&mut file, &record)?;
+
+        self.total_records += 1;
+
+        // A path that already had a live record on disk just had it
+        // superseded by this one, whether it's another upsert or a remove.
+        match &record {
+            SnapshotRecord::Upsert { path, .. } => {
+                if !self.live_paths.insert(path.clone()) {
+                    self.superseded_records += 1;
+                }
+            }
+            SnapshotRecord::Remove { path } => {
+                if self.live_paths.remove(path) {
+                    self.superseded_records += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn should_compact(&self) -> bool {
+        self.total_records > 0
+            && (self.superseded_records as f64 / self.total_records as f64)
+                > Self::COMPACTION_THRESHOLD
+    }
+
+    /// Rewrites the log as one upsert record per resident item, with no
+    /// superseded history, then swaps it in atomically.
+    fn compact<F: ImfsFetcher>(&mut self, imfs: &Imfs<F>) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        let mut record_count = 0u64;
+        let mut live_paths = HashSet::new();
+
+        {
+            let mut writer = io::BufWriter::new(fs::File::create(&tmp_path)?);
+
+            for (path, item) in imfs.inner.iter() {
+                write_record(
+                    &mut writer,
+                    &SnapshotRecord::Upsert {
+                        path: path.to_path_buf(),
+                        item: PersistedItem::from_item(item),
+                    },
+                )?;
+                record_count += 1;
+                live_paths.insert(path.to_path_buf());
+            }
+
+            writer.flush()?;
+        }
+
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.total_records = record_count;
+        self.superseded_records = 0;
+        self.live_paths = live_paths;
+
+        Ok(())
+    }
+}
+
+enum SnapshotRecord {
+    Upsert { path: PathBuf, item: PersistedItem },
+    Remove { path: PathBuf },
+}
+
+/// The on-disk representation of an `ImfsItem`: cached file contents are
+/// never persisted, only the stat info needed to validate them against disk
+/// via the mtime/digest fast path.
+enum PersistedItem {
+    File {
+        mtime: Option<TruncatedTimestamp>,
+        size: Option<u64>,
+        digest: Option<[u8; 32]>,
+    },
+    Directory {
+        children_enumerated: bool,
+    },
+    Symlink {
+        target: PathBuf,
+    },
+}
+
+impl PersistedItem {
+    fn from_item(item: &ImfsItem) -> Self {
+        match item {
+            ImfsItem::File(file) => PersistedItem::File {
+                mtime: file.mtime,
+                size: file.size,
+                digest: file.digest.map(|digest| *digest.as_bytes()),
+            },
+            ImfsItem::Directory(dir) => PersistedItem::Directory {
+                children_enumerated: dir.children_enumerated,
+            },
+            ImfsItem::Symlink(link) => PersistedItem::Symlink {
+                target: link.target.clone(),
+            },
+        }
+    }
+
+    fn into_imfs_item(self, path: PathBuf) -> ImfsItem {
+        match self {
+            PersistedItem::File { mtime, size, digest } => ImfsItem::File(ImfsFile {
+                path,
+                contents: None,
+                mtime,
+                size,
+                digest: digest.map(blake3::Hash::from),
+            }),
+            PersistedItem::Directory { children_enumerated } => {
+                ImfsItem::Directory(ImfsDirectory {
+                    path,
+                    children_enumerated,
+                })
+            }
+            PersistedItem::Symlink { target } => ImfsItem::Symlink(ImfsSymlink { path, target }),
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            PersistedItem::File { mtime, size, digest } => {
+                writer.write_all(&[ITEM_TAG_FILE])?;
+                write_option_mtime(writer, mtime)?;
+                write_option_u64(writer, *size)?;
+                write_option_digest(writer, digest)?;
+            }
+            PersistedItem::Directory { children_enumerated } => {
+                writer.write_all(&[ITEM_TAG_DIRECTORY, *children_enumerated as u8])?;
+            }
+            PersistedItem::Symlink { target } => {
+                writer.write_all(&[ITEM_TAG_SYMLINK])?;
+                write_path(writer, target)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut tag = [0u8; 1];
+        reader.read_exact(&mut tag)?;
+
+        match tag[0] {
+            ITEM_TAG_FILE => Ok(PersistedItem::File {
+                mtime: read_option_mtime(reader)?,
+                size: read_option_u64(reader)?,
+                digest: read_option_digest(reader)?,
+            }),
+            ITEM_TAG_DIRECTORY => {
+                let mut flag = [0u8; 1];
+                reader.read_exact(&mut flag)?;
+                Ok(PersistedItem::Directory {
+                    children_enumerated: flag[0] != 0,
+                })
+            }
+            ITEM_TAG_SYMLINK => Ok(PersistedItem::Symlink {
+                target: read_path(reader)?,
+            }),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unrecognized item tag in Imfs snapshot",
+            )),
+        }
+    }
+}
+
+const RECORD_TAG_UPSERT: u8 = 0;
+const RECORD_TAG_REMOVE: u8 = 1;
+
+const ITEM_TAG_FILE: u8 = 0;
+const ITEM_TAG_DIRECTORY: u8 = 1;
+const ITEM_TAG_SYMLINK: u8 = 2;
+
+fn write_record(writer: &mut impl Write, record: &SnapshotRecord) -> io::Result<()> {
+    match record {
+        SnapshotRecord::Upsert { path, item } => {
+            writer.write_all(&[RECORD_TAG_UPSERT])?;
+            write_path(writer, path)?;
+            item.write(writer)?;
+        }
+        SnapshotRecord::Remove { path } => {
+            writer.write_all(&[RECORD_TAG_REMOVE])?;
+            write_path(writer, path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_record(reader: &mut impl Read) -> io::Result<Option<SnapshotRecord>> {
+    let mut tag = [0u8; 1];
+
+    match reader.read_exact(&mut tag) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+
+    let path = read_path(reader)?;
+
+    let record = match tag[0] {
+        RECORD_TAG_UPSERT => SnapshotRecord::Upsert {
+            path,
+            item: PersistedItem::read(reader)?,
+        },
+        RECORD_TAG_REMOVE => SnapshotRecord::Remove { path },
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unrecognized record tag in Imfs snapshot",
+            ))
+        }
+    };
+
+    Ok(Some(record))
+}
+
+fn write_path(writer: &mut impl Write, path: &Path) -> io::Result<()> {
+    let encoded = path.to_string_lossy();
+    let bytes = encoded.as_bytes();
+
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_path(reader: &mut impl Read) -> io::Result<PathBuf> {
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes)?;
+    let len = u32::from_le_bytes(len_bytes) as usize;
+
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(PathBuf::from(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn write_option_u64(writer: &mut impl Write, value: Option<u64>) -> io::Result<()> {
+    match value {
+        Some(value) => {
+            writer.write_all(&[1])?;
+            writer.write_all(&value.to_le_bytes())
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn read_option_u64(reader: &mut impl Read) -> io::Result<Option<u64>> {
+    let mut present = [0u8; 1];
+    reader.read_exact(&mut present)?;
+
+    if present[0] == 0 {
+        return Ok(None);
+    }
+
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(Some(u64::from_le_bytes(bytes)))
+}
+
+fn write_option_mtime(writer: &mut impl Write, value: &Option<TruncatedTimestamp>) -> io::Result<()> {
+    match value {
+        Some(mtime) => {
+            writer.write_all(&[1])?;
+            writer.write_all(&mtime.seconds.to_le_bytes())?;
+            writer.write_all(&mtime.nanos.to_le_bytes())
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn read_option_mtime(reader: &mut impl Read) -> io::Result<Option<TruncatedTimestamp>> {
+    let mut present = [0u8; 1];
+    reader.read_exact(&mut present)?;
+
+    if present[0] == 0 {
+        return Ok(None);
+    }
+
+    let mut seconds_bytes = [0u8; 8];
+    reader.read_exact(&mut seconds_bytes)?;
+
+    let mut nanos_bytes = [0u8; 4];
+    reader.read_exact(&mut nanos_bytes)?;
+
+    Ok(Some(TruncatedTimestamp::new(
+        i64::from_le_bytes(seconds_bytes),
+        u32::from_le_bytes(nanos_bytes),
+    )))
+}
+
+fn write_option_digest(writer: &mut impl Write, value: &Option<[u8; 32]>) -> io::Result<()> {
+    match value {
+        Some(digest) => {
+            writer.write_all(&[1])?;
+            writer.write_all(digest)
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn read_option_digest(reader: &mut impl Read) -> io::Result<Option<[u8; 32]>> {
+    let mut present = [0u8; 1];
+    reader.read_exact(&mut present)?;
+
+    if present[0] == 0 {
+        return Ok(None);
+    }
+
+    let mut digest = [0u8; 32];
+    reader.read_exact(&mut digest)?;
+
+    Ok(Some(digest))
 }
 
 pub struct ImfsEntry {
     path: PathBuf,
     is_file: bool,
+    is_directory: bool,
+    target: Option<PathBuf>,
 }
 
 impl ImfsEntry {
@@ -104,6 +1074,7 @@ impl ImfsEntry {
         &self.path
     }
 
+    /// Reads this entry's contents, transparently following symlinks.
     pub fn contents<'imfs>(
         &self,
         imfs: &'imfs mut Imfs<impl ImfsFetcher>,
@@ -111,6 +1082,7 @@ impl ImfsEntry {
         imfs.get_contents(&self.path)
     }
 
+    /// Lists this entry's children, transparently following symlinks.
     pub fn children(
         &self,
         imfs: &mut Imfs<impl ImfsFetcher>,
@@ -123,13 +1095,24 @@ impl ImfsEntry {
     }
 
     pub fn is_directory(&self) -> bool {
-        !self.is_file
+        self.is_directory
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// The path this entry's symlink resolves to, if it is one.
+    pub fn target(&self) -> Option<&Path> {
+        self.target.as_deref()
     }
 }
 
+#[derive(Clone)]
 pub enum ImfsItem {
     File(ImfsFile),
     Directory(ImfsDirectory),
+    Symlink(ImfsSymlink),
 }
 
 impl ImfsItem {
@@ -137,16 +1120,872 @@ impl ImfsItem {
         match self {
             ImfsItem::File(file) => &file.path,
             ImfsItem::Directory(dir) => &dir.path,
+            ImfsItem::Symlink(link) => &link.path,
+        }
+    }
+
+    fn set_path(&mut self, path: PathBuf) {
+        match self {
+            ImfsItem::File(file) => file.path = path,
+            ImfsItem::Directory(dir) => dir.path = path,
+            ImfsItem::Symlink(link) => link.path = path,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct ImfsFile {
     pub(super) path: PathBuf,
     pub(super) contents: Option<Vec<u8>>,
+    pub(super) mtime: Option<TruncatedTimestamp>,
+    pub(super) size: Option<u64>,
+    pub(super) digest: Option<blake3::Hash>,
+}
+
+/// The outcome of comparing a file's cached stat info against a fresh read,
+/// used by `commit_change` to decide how much work a change event is worth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileStatus {
+    /// The mtime and size match the cache, and the mtime is old enough to be
+    /// trustworthy: the file is almost certainly unchanged.
+    Clean,
+    /// The size differs from the cache: the file has definitely changed.
+    Dirty,
+    /// The mtime/size comparison was inconclusive; only a content digest can
+    /// say for sure.
+    Unsure,
 }
 
+#[derive(Clone)]
 pub struct ImfsDirectory {
     pub(super) path: PathBuf,
     pub(super) children_enumerated: bool,
+}
+
+#[derive(Clone)]
+pub struct ImfsSymlink {
+    pub(super) path: PathBuf,
+    pub(super) target: PathBuf,
+}
+
+/// Recursively reads `item` and, if it's a directory, its descendants in
+/// parallel, returning every item discovered (including `item` itself).
+///
+/// A directory whose children can't be enumerated is treated as empty rather
+/// than failing the whole traversal.
+fn snapshot_subtree<F: ImfsFetcher + Sync>(fetcher: &F, item: ImfsItem) -> Vec<ImfsItem> {
+    let path = item.path().to_path_buf();
+    let mut results = vec![item];
+
+    if let ImfsItem::Directory(_) = &results[0] {
+        // A directory that fails to enumerate (e.g. permission denied) is
+        // recorded as empty-but-enumerated rather than aborting the walk.
+        if let Ok(children) = fetcher.read_children(&path) {
+            let subtrees: Vec<Vec<ImfsItem>> = children
+                .into_par_iter()
+                .map(|child| snapshot_subtree(fetcher, child))
+                .collect();
+
+            results.extend(subtrees.into_iter().flatten());
+        }
+
+        if let ImfsItem::Directory(dir) = &mut results[0] {
+            dir.children_enumerated = true;
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc;
+
+    /// A fetcher that never reaches real disk. The snapshot-store tests below
+    /// only need something that type-checks as `F: ImfsFetcher`; they drive
+    /// `Imfs` through its already-cached state rather than through reads.
+    struct NullFetcher;
+
+    impl ImfsFetcher for NullFetcher {
+        fn read_item(&self, _path: impl AsRef<Path>) -> io::Result<ImfsItem> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "NullFetcher has no items"))
+        }
+
+        fn read_children(&self, _path: impl AsRef<Path>) -> io::Result<Vec<ImfsItem>> {
+            Ok(Vec::new())
+        }
+
+        fn read_contents(&self, _path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn read_metadata(&self, _path: impl AsRef<Path>) -> io::Result<ImfsFileMetadata> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "NullFetcher has no items"))
+        }
+
+        fn create_directory(&self, _path: impl AsRef<Path>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn write_contents(&self, _path: impl AsRef<Path>, _contents: &[u8]) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn remove(&self, _path: impl AsRef<Path>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn rename(
+            &self,
+            _source: impl AsRef<Path>,
+            _target: impl AsRef<Path>,
+            _options: ImfsMoveOptions,
+        ) -> io::Result<bool> {
+            Ok(true)
+        }
+
+        fn copy(
+            &self,
+            _source: impl AsRef<Path>,
+            _target: impl AsRef<Path>,
+            _options: ImfsMoveOptions,
+        ) -> io::Result<bool> {
+            Ok(true)
+        }
+
+        fn watch(&self, _path: impl AsRef<Path>) {}
+
+        fn change_receiver(&self) -> Receiver<(PathBuf, ChangeKind)> {
+            mpsc::channel().1
+        }
+    }
+
+    /// A fetcher whose `rename`/`copy` are scripted to report whether they
+    /// actually moved/copied anything, rather than always succeeding like
+    /// `NullFetcher`. Used to exercise the case where a fetcher legitimately
+    /// no-ops against real disk state that the lazy cache doesn't know about.
+    struct ScriptedFetcher {
+        moves_succeed: bool,
+    }
+
+    impl ImfsFetcher for ScriptedFetcher {
+        fn read_item(&self, _path: impl AsRef<Path>) -> io::Result<ImfsItem> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "ScriptedFetcher has no items"))
+        }
+
+        fn read_children(&self, _path: impl AsRef<Path>) -> io::Result<Vec<ImfsItem>> {
+            Ok(Vec::new())
+        }
+
+        fn read_contents(&self, _path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn read_metadata(&self, _path: impl AsRef<Path>) -> io::Result<ImfsFileMetadata> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "ScriptedFetcher has no items"))
+        }
+
+        fn create_directory(&self, _path: impl AsRef<Path>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn write_contents(&self, _path: impl AsRef<Path>, _contents: &[u8]) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn remove(&self, _path: impl AsRef<Path>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn rename(
+            &self,
+            _source: impl AsRef<Path>,
+            _target: impl AsRef<Path>,
+            _options: ImfsMoveOptions,
+        ) -> io::Result<bool> {
+            Ok(self.moves_succeed)
+        }
+
+        fn copy(
+            &self,
+            _source: impl AsRef<Path>,
+            _target: impl AsRef<Path>,
+            _options: ImfsMoveOptions,
+        ) -> io::Result<bool> {
+            Ok(self.moves_succeed)
+        }
+
+        fn watch(&self, _path: impl AsRef<Path>) {}
+
+        fn change_receiver(&self) -> Receiver<(PathBuf, ChangeKind)> {
+            mpsc::channel().1
+        }
+    }
+
+    /// A fetcher whose `read_children` always returns a fixed listing,
+    /// regardless of path. Used to exercise how a directory change event
+    /// reconciles cached children against a fresh listing.
+    struct ChildrenFetcher {
+        children: Vec<ImfsItem>,
+    }
+
+    impl ImfsFetcher for ChildrenFetcher {
+        fn read_item(&self, _path: impl AsRef<Path>) -> io::Result<ImfsItem> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "ChildrenFetcher has no items"))
+        }
+
+        fn read_children(&self, _path: impl AsRef<Path>) -> io::Result<Vec<ImfsItem>> {
+            Ok(self.children.clone())
+        }
+
+        fn read_contents(&self, _path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn read_metadata(&self, _path: impl AsRef<Path>) -> io::Result<ImfsFileMetadata> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "ChildrenFetcher has no items"))
+        }
+
+        fn create_directory(&self, _path: impl AsRef<Path>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn write_contents(&self, _path: impl AsRef<Path>, _contents: &[u8]) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn remove(&self, _path: impl AsRef<Path>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn rename(
+            &self,
+            _source: impl AsRef<Path>,
+            _target: impl AsRef<Path>,
+            _options: ImfsMoveOptions,
+        ) -> io::Result<bool> {
+            Ok(true)
+        }
+
+        fn copy(
+            &self,
+            _source: impl AsRef<Path>,
+            _target: impl AsRef<Path>,
+            _options: ImfsMoveOptions,
+        ) -> io::Result<bool> {
+            Ok(true)
+        }
+
+        fn watch(&self, _path: impl AsRef<Path>) {}
+
+        fn change_receiver(&self) -> Receiver<(PathBuf, ChangeKind)> {
+            mpsc::channel().1
+        }
+    }
+
+    /// A fetcher backed by a fixed in-memory tree, used to exercise the
+    /// rayon `snapshot` traversal without touching real disk.
+    struct TreeFetcher {
+        items: std::collections::HashMap<PathBuf, ImfsItem>,
+        children: std::collections::HashMap<PathBuf, Vec<PathBuf>>,
+        unreadable: HashSet<PathBuf>,
+    }
+
+    impl ImfsFetcher for TreeFetcher {
+        fn read_item(&self, path: impl AsRef<Path>) -> io::Result<ImfsItem> {
+            self.items
+                .get(path.as_ref())
+                .cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not in TreeFetcher"))
+        }
+
+        fn read_children(&self, path: impl AsRef<Path>) -> io::Result<Vec<ImfsItem>> {
+            if self.unreadable.contains(path.as_ref()) {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "simulated unreadable directory"));
+            }
+
+            Ok(self
+                .children
+                .get(path.as_ref())
+                .into_iter()
+                .flatten()
+                .filter_map(|child_path| self.items.get(child_path).cloned())
+                .collect())
+        }
+
+        fn read_contents(&self, _path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn read_metadata(&self, _path: impl AsRef<Path>) -> io::Result<ImfsFileMetadata> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "not in TreeFetcher"))
+        }
+
+        fn create_directory(&self, _path: impl AsRef<Path>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn write_contents(&self, _path: impl AsRef<Path>, _contents: &[u8]) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn remove(&self, _path: impl AsRef<Path>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn rename(
+            &self,
+            _source: impl AsRef<Path>,
+            _target: impl AsRef<Path>,
+            _options: ImfsMoveOptions,
+        ) -> io::Result<bool> {
+            Ok(true)
+        }
+
+        fn copy(
+            &self,
+            _source: impl AsRef<Path>,
+            _target: impl AsRef<Path>,
+            _options: ImfsMoveOptions,
+        ) -> io::Result<bool> {
+            Ok(true)
+        }
+
+        fn watch(&self, _path: impl AsRef<Path>) {}
+
+        fn change_receiver(&self) -> Receiver<(PathBuf, ChangeKind)> {
+            mpsc::channel().1
+        }
+    }
+
+    fn imfs_with_fetcher<F: ImfsFetcher>(items: Vec<ImfsItem>, fetcher: F) -> Imfs<F> {
+        let mut inner = PathMap::new();
+
+        for item in items {
+            inner.insert(item.path().to_path_buf(), item);
+        }
+
+        Imfs { inner, fetcher }
+    }
+
+    fn imfs_with(items: Vec<ImfsItem>) -> Imfs<NullFetcher> {
+        imfs_with_fetcher(items, NullFetcher)
+    }
+
+    fn temp_snapshot_path(label: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("imfs-snapshot-store-{}-{}.bin", label, std::process::id()))
+    }
+
+    #[test]
+    fn commit_change_removed_purges_the_whole_cached_subtree() {
+        let dir = PathBuf::from("/project/src");
+        let file = dir.join("main.lua");
+
+        let mut imfs = imfs_with(vec![
+            ImfsItem::Directory(ImfsDirectory {
+                path: dir.clone(),
+                children_enumerated: true,
+            }),
+            ImfsItem::File(ImfsFile {
+                path: file.clone(),
+                contents: None,
+                mtime: None,
+                size: None,
+                digest: None,
+            }),
+        ]);
+
+        let change = imfs
+            .commit_change(&dir, ChangeKind::Removed)
+            .unwrap()
+            .expect("a resident path being removed should produce a change");
+
+        assert_eq!(change.kind, ChangeKind::Removed);
+        assert!(!imfs.inner.contains_key(&dir));
+        assert!(
+            !imfs.inner.contains_key(&file),
+            "removing a directory should purge its cached children, not just its own entry"
+        );
+    }
+
+    #[test]
+    fn rename_leaves_the_cache_untouched_when_the_fetcher_does_not_move() {
+        let source = PathBuf::from("/project/src/main.lua");
+        let target = PathBuf::from("/project/src/renamed.lua");
+
+        let mut imfs = imfs_with_fetcher(
+            vec![ImfsItem::File(ImfsFile {
+                path: source.clone(),
+                contents: None,
+                mtime: None,
+                size: None,
+                digest: None,
+            })],
+            ScriptedFetcher { moves_succeed: false },
+        );
+
+        // `target` isn't cached, so the in-memory check doesn't trip
+        // `ignore_if_exists`; only the fetcher, checking real disk, knows the
+        // rename should be skipped.
+        imfs.rename(
+            &source,
+            &target,
+            ImfsMoveOptions {
+                ignore_if_exists: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(
+            imfs.inner.contains_key(&source),
+            "a no-op rename shouldn't move source out of the cache"
+        );
+        assert!(
+            !imfs.inner.contains_key(&target),
+            "a no-op rename shouldn't re-key source onto target in the cache"
+        );
+    }
+
+    #[test]
+    fn copy_leaves_the_cache_untouched_when_the_fetcher_does_not_copy() {
+        let source = PathBuf::from("/project/src/main.lua");
+        let target = PathBuf::from("/project/src/copied.lua");
+
+        let mut imfs = imfs_with_fetcher(
+            vec![ImfsItem::File(ImfsFile {
+                path: source.clone(),
+                contents: None,
+                mtime: None,
+                size: None,
+                digest: None,
+            })],
+            ScriptedFetcher { moves_succeed: false },
+        );
+
+        imfs.copy(
+            &source,
+            &target,
+            ImfsMoveOptions {
+                ignore_if_exists: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(imfs.inner.contains_key(&source));
+        assert!(
+            !imfs.inner.contains_key(&target),
+            "a no-op copy shouldn't clone source onto target in the cache"
+        );
+    }
+
+    #[test]
+    fn commit_change_modified_on_a_directory_diffs_children_instead_of_overwriting() {
+        let dir = PathBuf::from("/project/src");
+        let untouched = dir.join("untouched.lua");
+        let removed = dir.join("removed.lua");
+        let added = dir.join("added.lua");
+
+        let fetcher = ChildrenFetcher {
+            children: vec![
+                ImfsItem::File(ImfsFile {
+                    path: untouched.clone(),
+                    contents: None,
+                    mtime: None,
+                    size: None,
+                    digest: None,
+                }),
+                ImfsItem::File(ImfsFile {
+                    path: added.clone(),
+                    contents: None,
+                    mtime: None,
+                    size: None,
+                    digest: None,
+                }),
+            ],
+        };
+
+        let mut imfs = imfs_with_fetcher(
+            vec![
+                ImfsItem::Directory(ImfsDirectory {
+                    path: dir.clone(),
+                    children_enumerated: true,
+                }),
+                ImfsItem::File(ImfsFile {
+                    path: untouched.clone(),
+                    contents: Some(b"cached".to_vec()),
+                    mtime: Some(TruncatedTimestamp::new(1_700_000_000, 0)),
+                    size: Some(6),
+                    digest: Some(blake3::hash(b"cached")),
+                }),
+                ImfsItem::File(ImfsFile {
+                    path: removed.clone(),
+                    contents: None,
+                    mtime: None,
+                    size: None,
+                    digest: None,
+                }),
+            ],
+            fetcher,
+        );
+
+        imfs.commit_change(&dir, ChangeKind::Modified).unwrap();
+
+        assert!(
+            !imfs.inner.contains_key(&removed),
+            "a child no longer in the fresh listing should be purged"
+        );
+        assert!(
+            imfs.inner.contains_key(&added),
+            "a new child in the fresh listing should be inserted"
+        );
+
+        match imfs.inner.get(&untouched) {
+            Some(ImfsItem::File(file)) => {
+                assert_eq!(
+                    file.contents.as_deref(),
+                    Some(b"cached".as_slice()),
+                    "an already-cached, still-present child shouldn't be clobbered by the fresh listing"
+                );
+            }
+            _ => panic!("expected untouched to still be cached as a file"),
+        }
+    }
+
+    #[test]
+    fn resolve_symlink_follows_a_chain_to_its_target() {
+        let a = PathBuf::from("/project/a");
+        let b = PathBuf::from("/project/b");
+        let c = PathBuf::from("/project/c");
+
+        let mut imfs = imfs_with(vec![
+            ImfsItem::Symlink(ImfsSymlink {
+                path: a.clone(),
+                target: b.clone(),
+            }),
+            ImfsItem::Symlink(ImfsSymlink {
+                path: b.clone(),
+                target: c.clone(),
+            }),
+            ImfsItem::File(ImfsFile {
+                path: c.clone(),
+                contents: None,
+                mtime: None,
+                size: None,
+                digest: None,
+            }),
+        ]);
+
+        assert_eq!(imfs.resolve_symlink(&a).unwrap(), c);
+    }
+
+    #[test]
+    fn resolve_symlink_detects_a_cycle() {
+        let a = PathBuf::from("/project/a");
+        let b = PathBuf::from("/project/b");
+
+        let mut imfs = imfs_with(vec![
+            ImfsItem::Symlink(ImfsSymlink {
+                path: a.clone(),
+                target: b.clone(),
+            }),
+            ImfsItem::Symlink(ImfsSymlink {
+                path: b.clone(),
+                target: a.clone(),
+            }),
+        ]);
+
+        assert!(
+            imfs.resolve_symlink(&a).is_err(),
+            "a symlink cycle should be reported as an error, not loop forever"
+        );
+    }
+
+    #[test]
+    fn rename_moves_a_cached_subtree() {
+        let dir = PathBuf::from("/project/src");
+        let file = dir.join("main.lua");
+        let new_dir = PathBuf::from("/project/lib");
+        let new_file = new_dir.join("main.lua");
+
+        let mut imfs = imfs_with_fetcher(
+            vec![
+                ImfsItem::Directory(ImfsDirectory {
+                    path: dir.clone(),
+                    children_enumerated: true,
+                }),
+                ImfsItem::File(ImfsFile {
+                    path: file.clone(),
+                    contents: None,
+                    mtime: None,
+                    size: None,
+                    digest: None,
+                }),
+            ],
+            ScriptedFetcher { moves_succeed: true },
+        );
+
+        imfs.rename(&dir, &new_dir, ImfsMoveOptions::default()).unwrap();
+
+        assert!(!imfs.inner.contains_key(&dir));
+        assert!(!imfs.inner.contains_key(&file));
+        assert!(imfs.inner.contains_key(&new_dir));
+        assert!(
+            imfs.inner.contains_key(&new_file),
+            "a renamed directory's cached children should be re-keyed under the new path too"
+        );
+    }
+
+    #[test]
+    fn copy_clones_a_cached_subtree() {
+        let dir = PathBuf::from("/project/src");
+        let file = dir.join("main.lua");
+        let new_dir = PathBuf::from("/project/lib");
+        let new_file = new_dir.join("main.lua");
+
+        let mut imfs = imfs_with_fetcher(
+            vec![
+                ImfsItem::Directory(ImfsDirectory {
+                    path: dir.clone(),
+                    children_enumerated: true,
+                }),
+                ImfsItem::File(ImfsFile {
+                    path: file.clone(),
+                    contents: None,
+                    mtime: None,
+                    size: None,
+                    digest: None,
+                }),
+            ],
+            ScriptedFetcher { moves_succeed: true },
+        );
+
+        imfs.copy(&dir, &new_dir, ImfsMoveOptions::default()).unwrap();
+
+        // Unlike a rename, the source subtree should still be cached too.
+        assert!(imfs.inner.contains_key(&dir));
+        assert!(imfs.inner.contains_key(&file));
+        assert!(imfs.inner.contains_key(&new_dir));
+        assert!(imfs.inner.contains_key(&new_file));
+    }
+
+    #[test]
+    fn snapshot_walks_the_whole_tree() {
+        let root = PathBuf::from("/project");
+        let child_dir = root.join("src");
+        let child_file = child_dir.join("main.lua");
+
+        let mut items = std::collections::HashMap::new();
+        items.insert(
+            root.clone(),
+            ImfsItem::Directory(ImfsDirectory {
+                path: root.clone(),
+                children_enumerated: false,
+            }),
+        );
+        items.insert(
+            child_dir.clone(),
+            ImfsItem::Directory(ImfsDirectory {
+                path: child_dir.clone(),
+                children_enumerated: false,
+            }),
+        );
+        items.insert(
+            child_file.clone(),
+            ImfsItem::File(ImfsFile {
+                path: child_file.clone(),
+                contents: None,
+                mtime: None,
+                size: None,
+                digest: None,
+            }),
+        );
+
+        let mut children = std::collections::HashMap::new();
+        children.insert(root.clone(), vec![child_dir.clone()]);
+        children.insert(child_dir.clone(), vec![child_file.clone()]);
+
+        let fetcher = TreeFetcher {
+            items,
+            children,
+            unreadable: HashSet::new(),
+        };
+        let mut imfs = Imfs {
+            inner: PathMap::new(),
+            fetcher,
+        };
+
+        imfs.snapshot(&root).unwrap();
+
+        assert!(imfs.inner.contains_key(&root));
+        assert!(imfs.inner.contains_key(&child_dir));
+        assert!(imfs.inner.contains_key(&child_file));
+
+        match imfs.inner.get(&root) {
+            Some(ImfsItem::Directory(dir)) => assert!(
+                dir.children_enumerated,
+                "a directory successfully walked by snapshot should be marked enumerated"
+            ),
+            _ => panic!("expected root to be cached as a directory"),
+        }
+    }
+
+    #[test]
+    fn snapshot_records_an_unreadable_directory_as_empty_but_enumerated() {
+        let root = PathBuf::from("/project");
+
+        let mut items = std::collections::HashMap::new();
+        items.insert(
+            root.clone(),
+            ImfsItem::Directory(ImfsDirectory {
+                path: root.clone(),
+                children_enumerated: false,
+            }),
+        );
+
+        let mut unreadable = HashSet::new();
+        unreadable.insert(root.clone());
+
+        let fetcher = TreeFetcher {
+            items,
+            children: std::collections::HashMap::new(),
+            unreadable,
+        };
+        let mut imfs = Imfs {
+            inner: PathMap::new(),
+            fetcher,
+        };
+
+        imfs.snapshot(&root).unwrap();
+
+        match imfs.inner.get(&root) {
+            Some(ImfsItem::Directory(dir)) => assert!(dir.children_enumerated),
+            _ => panic!("expected root to be cached as a directory"),
+        }
+    }
+
+    #[test]
+    fn record_round_trips_through_write_and_read() {
+        let record = SnapshotRecord::Upsert {
+            path: PathBuf::from("/project/src/main.lua"),
+            item: PersistedItem::File {
+                mtime: Some(TruncatedTimestamp::new(1_700_000_000, 42)),
+                size: Some(123),
+                digest: Some(*blake3::hash(b"contents").as_bytes()),
+            },
+        };
+
+        let mut buffer = Vec::new();
+        write_record(&mut buffer, &record).unwrap();
+
+        let mut reader = io::Cursor::new(buffer);
+        let read_back = read_record(&mut reader)
+            .unwrap()
+            .expect("a record should have been read back");
+
+        match (record, read_back) {
+            (
+                SnapshotRecord::Upsert {
+                    path: expected_path,
+                    item:
+                        PersistedItem::File {
+                            mtime: expected_mtime,
+                            size: expected_size,
+                            digest: expected_digest,
+                        },
+                },
+                SnapshotRecord::Upsert {
+                    path,
+                    item: PersistedItem::File { mtime, size, digest },
+                },
+            ) => {
+                assert_eq!(path, expected_path);
+                assert_eq!(mtime, expected_mtime);
+                assert_eq!(size, expected_size);
+                assert_eq!(digest, expected_digest);
+            }
+            _ => panic!("round-tripped record didn't match what was written"),
+        }
+
+        assert!(read_record(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn append_change_then_load_reconstructs_the_tree() {
+        let snapshot_path = temp_snapshot_path("roundtrip");
+        let _ = fs::remove_file(&snapshot_path);
+
+        let path = PathBuf::from("/project/src/main.lua");
+        let imfs = imfs_with(vec![ImfsItem::File(ImfsFile {
+            path: path.clone(),
+            contents: None,
+            mtime: Some(TruncatedTimestamp::new(1_700_000_000, 0)),
+            size: Some(4),
+            digest: None,
+        })]);
+
+        let mut store = ImfsSnapshotStore::new(&snapshot_path);
+        store
+            .append_change(
+                &imfs,
+                &ImfsChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Created,
+                },
+            )
+            .unwrap();
+
+        let mut reload = ImfsSnapshotStore::new(&snapshot_path);
+        let loaded = reload.load(NullFetcher).unwrap();
+
+        assert!(loaded.inner.contains_key(&path));
+        assert_eq!(reload.total_records, 1);
+        assert_eq!(reload.superseded_records, 0);
+
+        let _ = fs::remove_file(&snapshot_path);
+    }
+
+    #[test]
+    fn repeated_upserts_to_the_same_path_trigger_compaction() {
+        let snapshot_path = temp_snapshot_path("compaction");
+        let _ = fs::remove_file(&snapshot_path);
+
+        let path = PathBuf::from("/project/src/main.lua");
+        let mut store = ImfsSnapshotStore::new(&snapshot_path);
+
+        // Four upserts to the same path: the first three push the
+        // superseded ratio past `COMPACTION_THRESHOLD`, so the fourth
+        // `append_change` should compact before writing its own record
+        // instead of letting the log grow by one record per call.
+        for size in 0..4u64 {
+            let imfs = imfs_with(vec![ImfsItem::File(ImfsFile {
+                path: path.clone(),
+                contents: None,
+                mtime: Some(TruncatedTimestamp::new(1_700_000_000 + size as i64, 0)),
+                size: Some(size),
+                digest: None,
+            })]);
+
+            store
+                .append_change(
+                    &imfs,
+                    &ImfsChange {
+                        path: path.clone(),
+                        kind: ChangeKind::Modified,
+                    },
+                )
+                .unwrap();
+        }
+
+        // Without the compaction triggered on the last append, the log would
+        // hold 4 records with 3 superseded; compacting part-way through
+        // collapses that down to a fresh record plus the one just appended.
+        assert_eq!(store.total_records, 2);
+        assert_eq!(store.superseded_records, 1);
+        assert!(!store.should_compact());
+
+        let _ = fs::remove_file(&snapshot_path);
+    }
 }
\ No newline at end of file